@@ -7,7 +7,8 @@ use core::camera::{Camera, CameraSample};
 use core::film::Film;
 use core::floatfile::read_float_file;
 use core::geometry::{bnd2_expand, bnd2_union_pnt2, nrm_faceforward_vec3, pnt2_inside_bnd2};
-use core::geometry::{Bounds2f, Normal3f, Point2f, Point3f, Ray, Vector3f};
+use core::geometry::{Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Point3f, Ray, RayDifferential, Vector3f};
+use core::imageio::write_image;
 use core::interaction::InteractionCommon;
 use core::light::VisibilityTester;
 use core::lowdiscrepancy::radical_inverse;
@@ -39,6 +40,10 @@ pub struct RealisticCamera {
     pub simple_weighting: bool,
     pub element_interfaces: Vec<LensElementInterface>,
     pub exit_pupil_bounds: Vec<Bounds2f>,
+    // bladed (polygonal) aperture stop and anamorphic lens support
+    pub aperture_n_blades: i32,
+    pub aperture_rotation: Float,
+    pub anamorphic_squeeze: Float,
 }
 
 impl RealisticCamera {
@@ -52,6 +57,9 @@ impl RealisticCamera {
         lens_data: &Vec<Float>,
         film: Arc<Film>,
         medium: Option<Arc<Medium + Send + Sync>>,
+        aperture_n_blades: i32,
+        aperture_rotation: Float,
+        anamorphic_squeeze: Float,
     ) -> Self {
         let mut element_interfaces: Vec<LensElementInterface> = Vec::new();
         for i in (0..lens_data.len()).step_by(4) {
@@ -73,7 +81,7 @@ impl RealisticCamera {
             });
             println!("{:?}", element_interfaces[i / 4]);
         }
-        let camera = RealisticCamera {
+        let mut camera = RealisticCamera {
             camera_to_world: camera_to_world,
             shutter_open: shutter_open,
             shutter_close: shutter_close,
@@ -82,10 +90,23 @@ impl RealisticCamera {
             simple_weighting: simple_weighting,
             element_interfaces: element_interfaces,
             exit_pupil_bounds: Vec::new(),
+            aperture_n_blades: aperture_n_blades,
+            aperture_rotation: aperture_rotation,
+            anamorphic_squeeze: anamorphic_squeeze,
         };
         // compute lens--film distance for given focus distance
-        camera.focus_binary_search(focus_distance);
-        // WORK
+        let fb: Float = camera.focus_binary_search(focus_distance);
+        camera.element_interfaces.last_mut().unwrap().thickness = fb;
+        // compute exit pupil bounds at sampled points on the film
+        let n_samples: i32 = 64;
+        let mut exit_pupil_bounds: Vec<Bounds2f> = Vec::with_capacity(n_samples as usize);
+        for i in 0..n_samples {
+            let r0: Float = i as Float / n_samples as Float * camera.film.diagonal / 2.0 as Float;
+            let r1: Float =
+                (i + 1) as Float / n_samples as Float * camera.film.diagonal / 2.0 as Float;
+            exit_pupil_bounds.push(camera.bound_exit_pupil(r0, r1));
+        }
+        camera.exit_pupil_bounds = exit_pupil_bounds;
         camera
     }
     pub fn create(
@@ -117,6 +138,11 @@ impl RealisticCamera {
         let aperture_diameter: Float = params.find_one_float("aperturediameter", 1.0);
         let focus_distance: Float = params.find_one_float("focusdistance", 10.0);
         let simple_weighting: bool = params.find_one_bool("simpleweighting", true);
+        // polygonal (bladed) aperture stop; 0 (the default) keeps a circular stop
+        let aperture_n_blades: i32 = params.find_one_int("apertureblades", 0);
+        let aperture_rotation: Float = params.find_one_float("aperturerotation", 0.0);
+        // anamorphic squeeze factor applied to the sampled exit pupil point's $x$ axis
+        let anamorphic_squeeze: Float = params.find_one_float("apertureratio", 1.0);
         let mut lens_data: Vec<Float> = Vec::new();
         if !read_float_file(&lens_file, &mut lens_data) {
             println!(
@@ -139,6 +165,9 @@ impl RealisticCamera {
             &lens_data,
             film,
             medium,
+            aperture_n_blades,
+            aperture_rotation,
+            anamorphic_squeeze,
         ));
         camera
     }
@@ -156,6 +185,29 @@ impl RealisticCamera {
     pub fn rear_element_radius(&self) -> Float {
         self.element_interfaces.last().unwrap().aperture_radius
     }
+    pub fn point_in_aperture(&self, p_hit: &Point3f, element: &LensElementInterface) -> bool {
+        let x: Float = p_hit.x;
+        let y: Float = p_hit.y;
+        let is_stop: bool = element.curvature_radius == 0.0 as Float;
+        if !is_stop || self.aperture_n_blades < 3_i32 {
+            // every non-stop element keeps its plain circular rim; so does
+            // the stop itself when no polygonal aperture was requested
+            return x * x + y * y <= element.aperture_radius * element.aperture_radius;
+        }
+        // polygonal (bladed) aperture stop: the stop is the regular
+        // `aperture_n_blades`-gon inscribed in the circle of the element's
+        // aperture radius (its corners lie on that circle), rotated by
+        // `aperture_rotation` around the optical axis
+        let r: Float = (x * x + y * y).sqrt();
+        let mut theta: Float = y.atan2(x) - self.aperture_rotation;
+        let blade_angle: Float = 2.0 as Float * std::f32::consts::PI / self.aperture_n_blades as Float;
+        theta = theta - blade_angle * (theta / blade_angle).floor();
+        if theta > 0.5 as Float * blade_angle {
+            theta -= blade_angle;
+        }
+        let blade_radius: Float = element.aperture_radius * (0.5 as Float * blade_angle).cos();
+        r * theta.cos() <= blade_radius
+    }
     pub fn trace_lenses_from_film(&self, r_camera: &Ray, r_out: Option<&mut Ray>) -> bool {
         let mut element_z: Float = 0.0 as Float;
         // transform _rCamera_ from camera to lens system space
@@ -190,8 +242,7 @@ impl RealisticCamera {
             assert!(t >= 0.0 as Float);
             // test intersection point against element aperture
             let p_hit: Point3f = r_lens.position(t);
-            let r2: Float = p_hit.x * p_hit.x + p_hit.y * p_hit.y;
-            if r2 > element.aperture_radius * element.aperture_radius {
+            if !self.point_in_aperture(&p_hit, &element) {
                 return false;
             }
             r_lens.o = p_hit;
@@ -279,8 +330,7 @@ impl RealisticCamera {
             assert!(t >= 0.0 as Float);
             // test intersection point against element aperture
             let p_hit: Point3f = r_lens.position(t);
-            let r2: Float = p_hit.x * p_hit.x + p_hit.y * p_hit.y;
-            if r2 > element.aperture_radius * element.aperture_radius {
+            if !self.point_in_aperture(&p_hit, &element) {
                 return false;
             }
             r_lens.o = p_hit;
@@ -315,13 +365,193 @@ impl RealisticCamera {
         true
     }
     pub fn draw_lens_system(&self) {
-        // WORK
+        // emit a Mathematica `Graphics` primitive per lens element interface,
+        // one `Line`/`Circle` per line, ready to be pasted into a
+        // `Graphics[{...}]` expression for visual inspection of the lens system
+        let mut z: Float = -self.lens_front_z();
+        for element in &self.element_interfaces {
+            let r: Float = element.curvature_radius;
+            if r == 0.0 as Float {
+                // draw the aperture stop as two short line segments above/below the axis
+                println!(
+                    "Line[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    z,
+                    element.aperture_radius,
+                    z,
+                    1.5 as Float * element.aperture_radius
+                );
+                println!(
+                    "Line[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    z,
+                    -element.aperture_radius,
+                    z,
+                    -1.5 as Float * element.aperture_radius
+                );
+            } else {
+                // draw the spherical interface as a circular arc clipped to the aperture
+                let theta: Float = (element.aperture_radius / r).asin().abs();
+                if r > 0.0 as Float {
+                    println!(
+                        "Circle[{{{}, 0}}, {}, {{{}, {}}}]",
+                        z + r,
+                        r,
+                        std::f32::consts::PI - theta,
+                        std::f32::consts::PI + theta
+                    );
+                } else {
+                    println!("Circle[{{{}, 0}}, {}, {{{}, {}}}]", z + r, -r, -theta, theta);
+                }
+            }
+            z += element.thickness;
+        }
     }
     pub fn draw_ray_path_from_film(&self, r: &Ray, arrow: bool, to_optical_intercept: bool) {
-        // WORK
+        let mut element_z: Float = 0.0 as Float;
+        let camera_to_lens: Transform = Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+        let mut r_lens: Ray = camera_to_lens.transform_ray(r);
+        let mut points: Vec<Point3f> = vec![r_lens.o];
+        let mut exit_ray: Option<Ray> = None;
+        let ei_len = self.element_interfaces.len();
+        for idx in 0..ei_len {
+            let i = ei_len - 1 - idx;
+            let element = self.element_interfaces[i];
+            element_z -= element.thickness;
+            let mut t: Float = 0.0 as Float;
+            let mut n: Normal3f = Normal3f::default();
+            let is_stop: bool = element.curvature_radius == 0.0 as Float;
+            let intersected: bool = if is_stop {
+                if r_lens.d.z >= 0.0 as Float {
+                    false
+                } else {
+                    t = (element_z - r_lens.o.z) / r_lens.d.z;
+                    true
+                }
+            } else {
+                let radius: Float = element.curvature_radius;
+                let z_center: Float = element_z + element.curvature_radius;
+                self.intersect_spherical_element(radius, z_center, &r_lens, &mut t, &mut n)
+            };
+            if !intersected {
+                break;
+            }
+            let p_hit: Point3f = r_lens.position(t);
+            if !self.point_in_aperture(&p_hit, &element) {
+                break;
+            }
+            r_lens.o = p_hit;
+            points.push(p_hit);
+            if !is_stop {
+                let mut w: Vector3f = Vector3f::default();
+                let eta_i: Float = element.eta;
+                let eta_t: Float = if i > 0_usize && self.element_interfaces[i - 1].eta != 0.0 as Float {
+                    self.element_interfaces[i - 1].eta
+                } else {
+                    1.0 as Float
+                };
+                if !refract(&(-r_lens.d).normalize(), &n, eta_i / eta_t, &mut w) {
+                    break;
+                }
+                r_lens.d = w;
+            }
+            if i == 0_usize {
+                exit_ray = Some(r_lens);
+            }
+        }
+        if let Some(last_ray) = exit_ray {
+            let t_end: Float = if to_optical_intercept {
+                -last_ray.o.x / last_ray.d.x
+            } else {
+                1.0 as Float
+            };
+            points.push(last_ray.position(t_end));
+        }
+        for (i, pair) in points.windows(2).enumerate() {
+            let is_last: bool = i == points.len() - 2_usize;
+            if arrow && is_last {
+                println!(
+                    "Arrow[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    pair[0].z, pair[0].x, pair[1].z, pair[1].x
+                );
+            } else {
+                println!(
+                    "Line[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    pair[0].z, pair[0].x, pair[1].z, pair[1].x
+                );
+            }
+        }
     }
     pub fn draw_ray_path_from_scene(&self, r: &Ray, arrow: bool, to_optical_intercept: bool) {
-        // WORK
+        let mut element_z: Float = -self.lens_front_z();
+        let camera_to_lens: Transform = Transform::scale(1.0 as Float, 1.0 as Float, -1.0 as Float);
+        let mut r_lens: Ray = camera_to_lens.transform_ray(r);
+        let mut points: Vec<Point3f> = vec![r_lens.o];
+        let mut exit_ray: Option<Ray> = None;
+        for (i, element) in self.element_interfaces.iter().enumerate() {
+            let element = *element;
+            let mut t: Float = 0.0 as Float;
+            let mut n: Normal3f = Normal3f::default();
+            let is_stop: bool = element.curvature_radius == 0.0 as Float;
+            let intersected: bool = if is_stop {
+                t = (element_z - r_lens.o.z) / r_lens.d.z;
+                true
+            } else {
+                let radius: Float = element.curvature_radius;
+                let z_center: Float = element_z + element.curvature_radius;
+                self.intersect_spherical_element(radius, z_center, &r_lens, &mut t, &mut n)
+            };
+            if !intersected {
+                break;
+            }
+            let p_hit: Point3f = r_lens.position(t);
+            if !self.point_in_aperture(&p_hit, &element) {
+                break;
+            }
+            r_lens.o = p_hit;
+            points.push(p_hit);
+            if !is_stop {
+                let mut wt: Vector3f = Vector3f::default();
+                let eta_i: Float = if i == 0 || self.element_interfaces[i - 1].eta == 0.0 as Float {
+                    1.0 as Float
+                } else {
+                    self.element_interfaces[i - 1].eta
+                };
+                let eta_t: Float = if element.eta != 0.0 as Float {
+                    element.eta
+                } else {
+                    1.0 as Float
+                };
+                if !refract(&(-r_lens.d).normalize(), &n, eta_i / eta_t, &mut wt) {
+                    break;
+                }
+                r_lens.d = wt;
+            }
+            element_z += element.thickness;
+            if i == self.element_interfaces.len() - 1 {
+                exit_ray = Some(r_lens);
+            }
+        }
+        if let Some(last_ray) = exit_ray {
+            let t_end: Float = if to_optical_intercept {
+                -last_ray.o.x / last_ray.d.x
+            } else {
+                1.0 as Float
+            };
+            points.push(last_ray.position(t_end));
+        }
+        for (i, pair) in points.windows(2).enumerate() {
+            let is_last: bool = i == points.len() - 2_usize;
+            if arrow && is_last {
+                println!(
+                    "Arrow[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    pair[0].z, pair[0].x, pair[1].z, pair[1].x
+                );
+            } else {
+                println!(
+                    "Line[{{{{{}, {}}}, {{{}, {}}}}}]",
+                    pair[0].z, pair[0].x, pair[1].z, pair[1].x
+                );
+            }
+        }
     }
     pub fn compute_cardinal_points(
         &self,
@@ -403,56 +633,67 @@ impl RealisticCamera {
             film_distance_upper /= 1.005 as Float;
         }
         // do binary search on film distances to focus
-        // for (int i = 0; i < 20; ++i) {
-        //     Float fmid = 0.5f * (film_distance_lower + film_distance_upper);
-        //     Float midFocus = self.focus_distance(fmid);
-        //     if (midFocus < focus_distance)
-        //         film_distance_lower = fmid;
-        //     else
-        //         film_distance_upper = fmid;
-        // }
-        // return 0.5f * (film_distance_lower + film_distance_upper);
-        // WORK
-        0.0
+        for _i in 0..20 {
+            let fmid: Float = 0.5 as Float * (film_distance_lower + film_distance_upper);
+            let mid_focus: Float = self.focus_distance(fmid);
+            if mid_focus < focus_distance {
+                film_distance_lower = fmid;
+            } else {
+                film_distance_upper = fmid;
+            }
+        }
+        0.5 as Float * (film_distance_lower + film_distance_upper)
     }
     pub fn focus_distance(&self, film_dist: Float) -> Float {
         // find offset ray from film center through lens
         let bounds: Bounds2f =
             self.bound_exit_pupil(0.0 as Float, 0.001 as Float * self.film.diagonal);
-        // const std::array<Float, 3> scaleFactors = {0.1f, 0.01f, 0.001f};
-        // Float lu = 0.0f;
-
-        // Ray ray;
-
-        // // Try some different and decreasing scaling factor to find focus ray
-        // // more quickly when `aperturediameter` is too small.
-        // // (e.g. 2 [mm] for `aperturediameter` with wide.22mm.dat),
-        // bool foundFocusRay = false;
-        // for (Float scale : scaleFactors) {
-        //     lu = scale * bounds.pMax[0];
-        //     if (TraceLensesFromFilm(Ray(Point3f(0, 0, LensRearZ() - filmDistance),
-        //                                 Vector3f(lu, 0, filmDistance)),
-        //                             &ray)) {
-        //         foundFocusRay = true;
-        //         break;
-        //     }
-        // }
-
-        // if (!foundFocusRay) {
-        //     Error(
-        //         "Focus ray at lens pos(%f,0) didn't make it through the lenses "
-        //         "with film distance %f?!??\n",
-        //         lu, filmDistance);
-        //     return Infinity;
-        // }
-
-        // // Compute distance _zFocus_ where ray intersects the principal axis
-        // Float tFocus = -ray.o.x / ray.d.x;
-        // Float zFocus = ray(tFocus).z;
-        // if (zFocus < 0) zFocus = Infinity;
-        // return zFocus;
-        // WORK
-        0.0
+        let scale_factors: [Float; 3] = [0.1 as Float, 0.01 as Float, 0.001 as Float];
+        let mut lu: Float = 0.0 as Float;
+        let mut ray: Ray = Ray::default();
+        // try some different and decreasing scaling factor to find focus ray
+        // more quickly when `aperturediameter` is too small
+        // (e.g. 2 [mm] for `aperturediameter` with wide.22mm.dat)
+        let mut found_focus_ray: bool = false;
+        for scale in &scale_factors {
+            lu = *scale * bounds.p_max.x;
+            if self.trace_lenses_from_film(
+                &Ray {
+                    o: Point3f {
+                        x: 0.0 as Float,
+                        y: 0.0 as Float,
+                        z: self.lens_rear_z() - film_dist,
+                    },
+                    d: Vector3f {
+                        x: lu,
+                        y: 0.0 as Float,
+                        z: film_dist,
+                    },
+                    t_max: std::f32::INFINITY,
+                    time: 0.0 as Float,
+                    medium: None,
+                    differential: None,
+                },
+                Some(&mut ray),
+            ) {
+                found_focus_ray = true;
+                break;
+            }
+        }
+        if !found_focus_ray {
+            println!(
+                "Focus ray at lens pos({},0) didn't make it through the lenses with film distance {}?!??",
+                lu, film_dist
+            );
+            return std::f32::INFINITY;
+        }
+        // compute distance _z_focus_ where ray intersects the principal axis
+        let t_focus: Float = -ray.o.x / ray.d.x;
+        let mut z_focus: Float = ray.position(t_focus).z;
+        if z_focus < 0.0 as Float {
+            z_focus = std::f32::INFINITY;
+        }
+        z_focus
     }
     pub fn bound_exit_pupil(&self, p_film_x0: Float, p_film_x1: Float) -> Bounds2f {
         let mut pupil_bounds: Bounds2f = Bounds2f::default();
@@ -535,7 +776,61 @@ impl RealisticCamera {
         pupil_bounds
     }
     pub fn render_exit_pupil(&self, sx: Float, sy: Float, filename: String) {
-        // WORK
+        // rasterize the rear element plane, marking pixels by whether a ray
+        // from film point (sx, sy) through them makes it through the lens
+        // system, for visual inspection of the exit pupil's true shape; the
+        // extent is widened to 1.5x the rear element radius so the margin
+        // around the pupil is visible too
+        let p_film: Point3f = Point3f {
+            x: sx,
+            y: sy,
+            z: 0.0 as Float,
+        };
+        let n_side: i32 = 512;
+        let rear_radius: Float = 1.5 as Float * self.rear_element_radius();
+        let mut rgb: Vec<Float> = vec![0.0 as Float; (n_side * n_side * 3_i32) as usize];
+        for y in 0..n_side {
+            let fy: Float = y as Float / (n_side as Float - 1.0 as Float);
+            let ly: Float = lerp(fy, -rear_radius, rear_radius);
+            for x in 0..n_side {
+                let fx: Float = x as Float / (n_side as Float - 1.0 as Float);
+                let lx: Float = lerp(fx, -rear_radius, rear_radius);
+                if lx * lx + ly * ly > rear_radius * rear_radius {
+                    continue;
+                }
+                let p_rear: Point3f = Point3f {
+                    x: lx,
+                    y: ly,
+                    z: self.lens_rear_z(),
+                };
+                if self.trace_lenses_from_film(
+                    &Ray {
+                        o: p_film,
+                        d: p_rear - p_film,
+                        t_max: std::f32::INFINITY,
+                        time: 0.0 as Float,
+                        medium: None,
+                        differential: None,
+                    },
+                    None,
+                ) {
+                    let idx: usize = ((y * n_side + x) * 3_i32) as usize;
+                    rgb[idx] = 1.0 as Float;
+                    rgb[idx + 1] = 1.0 as Float;
+                    rgb[idx + 2] = 1.0 as Float;
+                }
+            }
+        }
+        let resolution: Point2i = Point2i {
+            x: n_side,
+            y: n_side,
+        };
+        let output_bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: resolution,
+        };
+        write_image(&filename, &rgb, output_bounds, resolution);
+        println!("Wrote exit pupil image to {:?}", filename);
     }
     pub fn sample_exit_pupil(
         &self,
@@ -543,18 +838,188 @@ impl RealisticCamera {
         lens_sample: &Point2f,
         sample_bounds_area: &mut Float,
     ) -> Point3f {
-        // WORK
-        Point3f::default()
+        // find exit pupil bound for sample distance from film center
+        let r_film: Float = (p_film.x * p_film.x + p_film.y * p_film.y).sqrt();
+        let mut r_index: usize =
+            (r_film / (self.film.diagonal / 2.0 as Float) * self.exit_pupil_bounds.len() as Float)
+                as usize;
+        r_index = std::cmp::min(self.exit_pupil_bounds.len() - 1_usize, r_index);
+        let pupil_bounds: Bounds2f = self.exit_pupil_bounds[r_index];
+        *sample_bounds_area = pupil_bounds.area();
+        // generate sample point inside exit pupil bound
+        let p_lens: Point2f = pupil_bounds.lerp(lens_sample);
+        // return sample point rotated by angle of _p_film_ with $+x$ axis
+        let sin_theta: Float = if r_film != 0.0 as Float {
+            p_film.y / r_film
+        } else {
+            0.0 as Float
+        };
+        let cos_theta: Float = if r_film != 0.0 as Float {
+            p_film.x / r_film
+        } else {
+            1.0 as Float
+        };
+        Point3f {
+            // the anamorphic squeeze is applied here, to the sampled pupil
+            // point, rather than to the aperture test so that every lens
+            // element's rim stays circular and only the ray bundle is squeezed
+            x: (cos_theta * p_lens.x - sin_theta * p_lens.y) * self.anamorphic_squeeze,
+            y: sin_theta * p_lens.x + cos_theta * p_lens.y,
+            z: self.lens_rear_z(),
+        }
     }
     pub fn test_exit_pupil_bounds(&self) {
-        // WORK
+        // randomly sample film points and individually brute-force trace
+        // rear-element points for each, rather than recomputing
+        // `bound_exit_pupil` with the very (r0, r1) used to build
+        // `exit_pupil_bounds`; this actually exercises whether the cached
+        // bounds under-cover the true exit pupil for rays that are known to
+        // make it through the lens system
+        let film_diagonal: Float = self.film.diagonal;
+        let rear_radius: Float = self.rear_element_radius();
+        let n_samples: i32 = self.exit_pupil_bounds.len() as i32;
+        let n_trials: i32 = 4096;
+        let mut n_missed: i32 = 0;
+        for trial in 0..n_trials {
+            let u: [Float; 4] = [
+                radical_inverse(0 as u16, trial as u64),
+                radical_inverse(1 as u16, trial as u64),
+                radical_inverse(2 as u16, trial as u64),
+                radical_inverse(3 as u16, trial as u64),
+            ];
+            let r_film: Float = u[0] * film_diagonal / 2.0 as Float;
+            let p_film: Point3f = Point3f {
+                x: r_film,
+                y: 0.0 as Float,
+                z: 0.0 as Float,
+            };
+            let p_rear: Point3f = Point3f {
+                x: lerp(u[1], -1.5 as Float * rear_radius, 1.5 as Float * rear_radius),
+                y: lerp(u[2], -1.5 as Float * rear_radius, 1.5 as Float * rear_radius),
+                z: self.lens_rear_z(),
+            };
+            let made_it: bool = self.trace_lenses_from_film(
+                &Ray {
+                    o: p_film,
+                    d: p_rear - p_film,
+                    t_max: std::f32::INFINITY,
+                    time: u[3],
+                    medium: None,
+                    differential: None,
+                },
+                None,
+            );
+            if made_it {
+                let mut r_index: usize =
+                    (r_film / (film_diagonal / 2.0 as Float) * n_samples as Float) as usize;
+                r_index = std::cmp::min(n_samples as usize - 1_usize, r_index);
+                let cached: Bounds2f = self.exit_pupil_bounds[r_index];
+                let p_rear_2d: Point2f = Point2f {
+                    x: p_rear.x,
+                    y: p_rear.y,
+                };
+                if !pnt2_inside_bnd2(&p_rear_2d, &cached) {
+                    n_missed += 1_i32;
+                    println!(
+                        "exit pupil bounds[{}] does not cover traced point {:?} (r_film = {})",
+                        r_index, p_rear_2d, r_film
+                    );
+                }
+            }
+        }
+        println!(
+            "test_exit_pupil_bounds: {} of {} traced rear-element points fell outside the cached exit pupil bounds",
+            n_missed, n_trials
+        );
+        assert_eq!(
+            n_missed, 0_i32,
+            "cached exit_pupil_bounds under-cover the true exit pupil"
+        );
+    }
+    pub fn generate_ray(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
+        // find point on film, _p_film_, corresponding to _sample.p_film_
+        let s: Point2f = Point2f {
+            x: sample.p_film.x / self.film.full_resolution.x as Float,
+            y: sample.p_film.y / self.film.full_resolution.y as Float,
+        };
+        let p_film2: Point2f = self.film.get_physical_extent().lerp(&s);
+        let p_film: Point3f = Point3f {
+            x: -p_film2.x,
+            y: p_film2.y,
+            z: 0.0 as Float,
+        };
+        // trace ray from _p_film_ through lens system
+        let mut exit_pupil_bounds_area: Float = 0.0 as Float;
+        let p_rear: Point3f = self.sample_exit_pupil(
+            &Point2f {
+                x: p_film.x,
+                y: p_film.y,
+            },
+            &sample.p_lens,
+            &mut exit_pupil_bounds_area,
+        );
+        let r_film: Ray = Ray {
+            o: p_film,
+            d: p_rear - p_film,
+            t_max: std::f32::INFINITY,
+            time: lerp(sample.time, self.shutter_open, self.shutter_close),
+            medium: None,
+            differential: None,
+        };
+        if !self.trace_lenses_from_film(&r_film, Some(ray)) {
+            return 0.0 as Float;
+        }
+        // finish initialization of _RealisticCamera_ ray
+        *ray = self.camera_to_world.transform_ray(ray);
+        ray.d = ray.d.normalize();
+        ray.medium = self.medium.clone();
+        // return weighting for _RealisticCamera_ ray
+        let cos_theta: Float = r_film.d.normalize().z;
+        let cos4_theta: Float = (cos_theta * cos_theta) * (cos_theta * cos_theta);
+        if self.simple_weighting {
+            cos4_theta
+        } else {
+            (self.shutter_close - self.shutter_open) * (cos4_theta * exit_pupil_bounds_area)
+                / (self.lens_rear_z() * self.lens_rear_z())
+        }
     }
 }
 
 impl Camera for RealisticCamera {
     fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // WORK
-        0.0
+        let wt: Float = self.generate_ray(sample, ray);
+        if wt == 0.0 as Float {
+            return 0.0 as Float;
+        }
+        // find ray after shifting one pixel in the $x$ direction
+        let mut sshift: CameraSample = CameraSample {
+            p_film: Point2f {
+                x: sample.p_film.x + 1.0 as Float,
+                y: sample.p_film.y,
+            },
+            p_lens: sample.p_lens,
+            time: sample.time,
+        };
+        let mut rx: Ray = Ray::default();
+        let wtx: Float = self.generate_ray(&sshift, &mut rx);
+        if wtx == 0.0 as Float {
+            return 0.0 as Float;
+        }
+        // find ray after shifting one pixel in the $y$ direction
+        sshift.p_film.x = sample.p_film.x;
+        sshift.p_film.y = sample.p_film.y + 1.0 as Float;
+        let mut ry: Ray = Ray::default();
+        let wty: Float = self.generate_ray(&sshift, &mut ry);
+        if wty == 0.0 as Float {
+            return 0.0 as Float;
+        }
+        ray.differential = Some(RayDifferential {
+            rx_origin: rx.o,
+            ry_origin: ry.o,
+            rx_direction: rx.d,
+            ry_direction: ry.d,
+        });
+        wt
     }
     fn we(&self, _ray: &Ray, _p_raster2: Option<&mut Point2f>) -> Spectrum {
         panic!("camera::we() is not implemented!");
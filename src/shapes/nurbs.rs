@@ -1,8 +1,15 @@
 // std
 use std;
+use std::sync::Arc;
+// ndarray
+use ndarray::Array2;
 // pbrt
-use core::geometry::{Point3f, Vector3f};
-use core::pbrt::Float;
+use core::geometry::{nrm_normalize, vec3_cross_vec3, Normal3f, Point2f, Point3f, Vector3f};
+use core::paramset::ParamSet;
+use core::pbrt::{lerp, Float};
+use core::shape::Shape;
+use core::transform::Transform;
+use shapes::triangle::create_triangle_mesh;
 
 // see nurbs.cpp
 
@@ -18,31 +25,178 @@ pub fn knot_offset(knot: &Vec<Float>, order: i32, np: i32, t: Float) -> usize {
     knot_offset
 }
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct Homogeneous3 {
-    pub x: Float,
-    pub y: Float,
-    pub z: Float,
-    pub w: Float,
+/// A control point usable by the generic NURBS evaluation routines below.
+/// `HomogeneousPoint` is the rational case (a trailing weight `w`, divided
+/// through at the end); `EuclideanPoint` is the non-rational case (a plain
+/// position, evaluated with no perspective divide at all).
+pub trait NurbsControlPoint: Clone {
+    fn zero(n_components: usize) -> Self;
+    fn n_components(&self) -> usize;
+    fn get(&self, k: usize) -> Float;
+    fn set(&mut self, k: usize, value: Float);
+    /// Turn the blended components into a 3D point.
+    fn eval_point(&self) -> Point3f;
+    /// Turn the blended components' raw rate of change (`delta`, already
+    /// scaled by the knot-span factor) into a tangent vector.
+    fn eval_derivative(&self, delta: &Self) -> Vector3f;
 }
 
-pub fn nurbs_evaluate(
+/// A control point in homogeneous coordinates, generalized to an
+/// arbitrary number of components (the last component is always the
+/// weight `w`). A 3D rational surface or curve uses 4 components
+/// (x, y, z, w).
+#[derive(Debug, Clone)]
+pub struct HomogeneousPoint {
+    pub c: Vec<Float>,
+}
+
+impl HomogeneousPoint {
+    pub fn new(n_components: usize) -> Self {
+        HomogeneousPoint {
+            c: vec![0.0 as Float; n_components],
+        }
+    }
+    pub fn from_components(c: Vec<Float>) -> Self {
+        HomogeneousPoint { c }
+    }
+    pub fn xyzw(x: Float, y: Float, z: Float, w: Float) -> Self {
+        HomogeneousPoint { c: vec![x, y, z, w] }
+    }
+    pub fn n_components(&self) -> usize {
+        self.c.len()
+    }
+    pub fn w(&self) -> Float {
+        *self.c.last().unwrap()
+    }
+}
+
+impl NurbsControlPoint for HomogeneousPoint {
+    fn zero(n_components: usize) -> Self {
+        HomogeneousPoint::new(n_components)
+    }
+    fn n_components(&self) -> usize {
+        HomogeneousPoint::n_components(self)
+    }
+    fn get(&self, k: usize) -> Float {
+        self.c[k]
+    }
+    fn set(&mut self, k: usize, value: Float) {
+        self.c[k] = value;
+    }
+    fn eval_point(&self) -> Point3f {
+        Point3f {
+            x: self.c[0] / self.w(),
+            y: self.c[1] / self.w(),
+            z: self.c[2] / self.w(),
+        }
+    }
+    fn eval_derivative(&self, delta: &HomogeneousPoint) -> Vector3f {
+        // the perspective divide: d/dt (p / w) = dp/dt / w - p * dw/dt / w^2
+        let w: Float = self.w();
+        let dw: Float = delta.w();
+        Vector3f {
+            x: delta.c[0] / w - (self.c[0] * dw / (w * w)),
+            y: delta.c[1] / w - (self.c[1] * dw / (w * w)),
+            z: delta.c[2] / w - (self.c[2] * dw / (w * w)),
+        }
+    }
+}
+
+/// A non-rational control point: a plain (x, y, z) position with no
+/// homogeneous weight. Evaluation skips the perspective divide that
+/// `HomogeneousPoint` requires.
+#[derive(Debug, Clone)]
+pub struct EuclideanPoint {
+    pub c: Vec<Float>,
+}
+
+impl EuclideanPoint {
+    pub fn new(n_components: usize) -> Self {
+        EuclideanPoint {
+            c: vec![0.0 as Float; n_components],
+        }
+    }
+    pub fn xyz(x: Float, y: Float, z: Float) -> Self {
+        EuclideanPoint { c: vec![x, y, z] }
+    }
+}
+
+impl NurbsControlPoint for EuclideanPoint {
+    fn zero(n_components: usize) -> Self {
+        EuclideanPoint::new(n_components)
+    }
+    fn n_components(&self) -> usize {
+        self.c.len()
+    }
+    fn get(&self, k: usize) -> Float {
+        self.c[k]
+    }
+    fn set(&mut self, k: usize, value: Float) {
+        self.c[k] = value;
+    }
+    fn eval_point(&self) -> Point3f {
+        Point3f {
+            x: self.c[0],
+            y: self.c[1],
+            z: self.c[2],
+        }
+    }
+    fn eval_derivative(&self, delta: &EuclideanPoint) -> Vector3f {
+        Vector3f {
+            x: delta.c[0],
+            y: delta.c[1],
+            z: delta.c[2],
+        }
+    }
+}
+
+/// A rectangular NURBS control net, stored as an `ndarray` grid indexed
+/// `[v][u]` so that rows and columns can be pulled out of the patch
+/// without manual stride arithmetic.
+pub struct ControlNet<P: NurbsControlPoint> {
+    pub grid: Array2<P>,
+}
+
+impl<P: NurbsControlPoint> ControlNet<P> {
+    pub fn new(nu: usize, nv: usize, cp: Vec<P>) -> Self {
+        assert_eq!(cp.len(), nu * nv);
+        let grid: Array2<P> = Array2::from_shape_vec((nv, nu), cp).unwrap();
+        ControlNet { grid }
+    }
+    pub fn nu(&self) -> usize {
+        self.grid.ncols()
+    }
+    pub fn nv(&self) -> usize {
+        self.grid.nrows()
+    }
+    /// All control points sharing `v_index`, in order of increasing `u`.
+    pub fn u_row(&self, v_index: usize) -> Vec<P> {
+        self.grid.row(v_index).to_vec()
+    }
+    /// All control points sharing `u_index`, in order of increasing `v`.
+    pub fn v_column(&self, u_index: usize) -> Vec<P> {
+        self.grid.column(u_index).to_vec()
+    }
+}
+
+pub fn nurbs_evaluate<P: NurbsControlPoint>(
     order: i32,
     knot: &Vec<Float>,
-    cp: &Vec<Homogeneous3>,
-    cp_start: usize,
+    cp: &[P],
+    cp_start: i32,
     np: i32,
-    cp_stride: i32,
     t: Float,
-    // TODO: deriv,
-) -> Homogeneous3 {
+    deriv: Option<&mut Vector3f>,
+) -> P {
     let mut alpha: Float = 0.0;
     let knot_offset: usize = knot_offset(knot, order, np, t);
     let cp_offset: usize = knot_offset + 1 - order as usize;
-    assert!(cp_offset >= 0 && cp_offset < np as usize);
-    let mut cp_work: Vec<Homogeneous3> = Vec::with_capacity(order as usize);
+    assert!(cp_offset < np as usize);
+    let n_components: usize = cp[0].n_components();
+    let mut cp_work: Vec<P> = Vec::with_capacity(order as usize);
     for i in 0..order {
-        cp_work.push(cp[cp_start + (cp_offset + i as usize) * cp_stride as usize]);
+        let idx: i32 = cp_start + cp_offset as i32 + i;
+        cp_work.push(cp[idx as usize].clone());
     }
     for i in 0..(order - 2) {
         for j in 0..(order - 1 - i) {
@@ -51,79 +205,352 @@ pub fn nurbs_evaluate(
                     - knot[(knot_offset as i32 + (j + 2 + i - order)) as usize]);
             assert!(alpha >= 0.0 as Float && alpha <= 1.0 as Float);
             let one_minus_alpha: Float = 1.0 as Float - alpha;
-            cp_work[j as usize].x =
-                cp_work[j as usize].x * alpha + cp_work[(j + 1) as usize].x * one_minus_alpha;
-            cp_work[j as usize].y =
-                cp_work[j as usize].y * alpha + cp_work[(j + 1) as usize].y * one_minus_alpha;
-            cp_work[j as usize].z =
-                cp_work[j as usize].z * alpha + cp_work[(j + 1) as usize].z * one_minus_alpha;
-            cp_work[j as usize].w =
-                cp_work[j as usize].w * alpha + cp_work[(j + 1) as usize].w * one_minus_alpha;
+            for k in 0..n_components {
+                let blended: Float = cp_work[j as usize].get(k) * alpha
+                    + cp_work[(j + 1) as usize].get(k) * one_minus_alpha;
+                cp_work[j as usize].set(k, blended);
+            }
         }
     }
     alpha = (knot[knot_offset + 1] - t) / (knot[knot_offset + 1] - knot[knot_offset + 0]);
     assert!(alpha >= 0.0 as Float && alpha <= 1.0 as Float);
     let one_minus_alpha: Float = 1.0 as Float - alpha;
-    let val: Homogeneous3 = Homogeneous3{
-        x: cp_work[0].x * alpha + cp_work[1].x * one_minus_alpha,
-        y: cp_work[0].y * alpha + cp_work[1].y * one_minus_alpha,
-        z: cp_work[0].z * alpha + cp_work[1].z * one_minus_alpha,
-        w: cp_work[0].w * alpha + cp_work[1].w * one_minus_alpha
-    };
-    // if (deriv) {
-    //     Float factor = (order - 1) / (knot[knot_offset + 1] - knot[knot_offset + 0]);
-    //     Homogeneous3 delta((cp_work[1].x - cp_work[0].x) * factor,
-    //                        (cp_work[1].y - cp_work[0].y) * factor,
-    //                        (cp_work[1].z - cp_work[0].z) * factor,
-    //                        (cp_work[1].w - cp_work[0].w) * factor);
-
-    //     deriv->x = delta.x / val.w - (val.x * delta.w / (val.w * val.w));
-    //     deriv->y = delta.y / val.w - (val.y * delta.w / (val.w * val.w));
-    //     deriv->z = delta.z / val.w - (val.z * delta.w / (val.w * val.w));
-    // }
+    let mut val: P = P::zero(n_components);
+    for k in 0..n_components {
+        val.set(k, cp_work[0].get(k) * alpha + cp_work[1].get(k) * one_minus_alpha);
+    }
+    if let Some(deriv) = deriv {
+        let factor: Float =
+            (order - 1_i32) as Float / (knot[knot_offset + 1] - knot[knot_offset + 0]);
+        let mut delta: P = P::zero(n_components);
+        for k in 0..n_components {
+            delta.set(k, (cp_work[1].get(k) - cp_work[0].get(k)) * factor);
+        }
+        *deriv = val.eval_derivative(&delta);
+    }
     val
 }
 
-pub fn nurbs_evaluate_surface(
+pub fn nurbs_evaluate_surface<P: NurbsControlPoint>(
     u_order: i32,
     u_knot: &Vec<Float>,
-    ucp: i32,
     u: Float,
     v_order: i32,
     v_knot: &Vec<Float>,
-    vcp: i32,
     v: Float,
-    cp: &Vec<Homogeneous3>,
+    cp: &ControlNet<P>,
     dpdu: &mut Vector3f,
-    dpfc: &mut Vector3f,
+    dpdv: &mut Vector3f,
 ) -> Point3f {
-    let mut iso: Vec<Homogeneous3> = Vec::with_capacity(std::cmp::max(u_order, v_order) as usize);
+    let ucp: i32 = cp.nu() as i32;
+    let vcp: i32 = cp.nv() as i32;
     let u_offset: usize = knot_offset(u_knot, u_order, ucp, u);
     let u_first_cp: usize = u_offset + 1 - u_order as usize;
-    assert!(u_first_cp >= 0 && u_first_cp + u_order as usize - 1 < ucp as usize);
-    for i in 0..u_order {
-        iso.push(nurbs_evaluate(
-            v_order,
-            v_knot,
-            &cp,
-            u_first_cp + i as usize,
-            vcp,
-            ucp,
-            v,
-        ));
+    assert!(u_first_cp + u_order as usize - 1 < ucp as usize);
+    // evaluate the isoparametric curve at $v$ for each control column
+    // touched by $u$'s knot span; `ndarray` supplies the column without
+    // any manual stride bookkeeping
+    let mut iso: Vec<P> = Vec::with_capacity(u_order as usize);
+    for i in 0..u_order as usize {
+        let column: Vec<P> = cp.v_column(u_first_cp + i);
+        iso.push(nurbs_evaluate(v_order, v_knot, &column, 0, vcp, v, None));
     }
     let v_offset: usize = knot_offset(v_knot, v_order, vcp, v);
-    // int v_first_cp = v_offset - v_order + 1;
-    // CHECK(v_first_cp >= 0 && v_first_cp + v_order - 1 < vcp);
-    // Homogeneous3 P =
-    //     NURBSEvaluate(u_order, u_knot, iso - u_first_cp, ucp, 1, u, dpdu);
-    // if (dpdv) {
-    //     for (int i = 0; i < v_order; ++i)
-    //         iso[i] = NURBSEvaluate(u_order, u_knot, &cp[(v_first_cp + i) * ucp],
-    //                                ucp, 1, u);
-    //     (void)NURBSEvaluate(v_order, v_knot, iso - v_first_cp, vcp, 1, v, dpdv);
-    // }
-    // return Point3f(P.x / P.w, P.y / P.w, P.z / P.w);
-    // WORK
-    Point3f::default()
+    let v_first_cp: usize = v_offset + 1 - v_order as usize;
+    assert!(v_first_cp + v_order as usize - 1 < vcp as usize);
+    let p: P = nurbs_evaluate(
+        u_order,
+        u_knot,
+        &iso,
+        -(u_first_cp as i32),
+        ucp,
+        u,
+        Some(dpdu),
+    );
+    // _dpdv_ is computed as a byproduct of evaluating the isoparametric
+    // curve in $v$ through the control rows touched by $v$'s knot span
+    let mut iso_v: Vec<P> = Vec::with_capacity(v_order as usize);
+    for i in 0..v_order as usize {
+        let row: Vec<P> = cp.u_row(v_first_cp + i);
+        iso_v.push(nurbs_evaluate(u_order, u_knot, &row, 0, ucp, u, None));
+    }
+    nurbs_evaluate(
+        v_order,
+        v_knot,
+        &iso_v,
+        -(v_first_cp as i32),
+        vcp,
+        v,
+        Some(dpdv),
+    );
+    p.eval_point()
+}
+
+/// Insert a single knot `t_new` into a NURBS curve via Boehm's algorithm,
+/// returning the refined knot vector and the corresponding refined
+/// control points.
+pub fn knot_insert<P: NurbsControlPoint>(
+    order: i32,
+    knot: &Vec<Float>,
+    cp: &Vec<P>,
+    t_new: Float,
+) -> (Vec<Float>, Vec<P>) {
+    let np: i32 = cp.len() as i32;
+    let k: usize = knot_offset(knot, order, np, t_new);
+    let n_components: usize = cp[0].n_components();
+    let mut new_knot: Vec<Float> = knot.clone();
+    new_knot.insert(k + 1, t_new);
+    let mut new_cp: Vec<P> = Vec::with_capacity(cp.len() + 1_usize);
+    for i in 0..=cp.len() {
+        let point: P = if i as i32 <= k as i32 - order + 1_i32 {
+            cp[i].clone()
+        } else if i as i32 > k as i32 {
+            cp[i - 1_usize].clone()
+        } else {
+            let alpha: Float =
+                (t_new - knot[i]) / (knot[i + (order - 1_i32) as usize] - knot[i]);
+            let one_minus_alpha: Float = 1.0 as Float - alpha;
+            let mut blended: P = P::zero(n_components);
+            for k in 0..n_components {
+                blended.set(
+                    k,
+                    one_minus_alpha * cp[i - 1_usize].get(k) + alpha * cp[i].get(k),
+                );
+            }
+            blended
+        };
+        new_cp.push(point);
+    }
+    (new_knot, new_cp)
+}
+
+/// Decompose a NURBS curve into a sequence of Bézier segments by running
+/// Boehm's knot-insertion algorithm until every interior knot reaches
+/// multiplicity `order - 1`; each span between distinct knot values then
+/// corresponds to an independent, order-many-control-point Bézier curve.
+pub fn nurbs_to_bezier_segments<P: NurbsControlPoint>(
+    order: i32,
+    knot: &Vec<Float>,
+    cp: &Vec<P>,
+) -> Vec<Vec<P>> {
+    let mut cur_knot: Vec<Float> = knot.clone();
+    let mut cur_cp: Vec<P> = cp.clone();
+    let mut i: usize = order as usize;
+    while i + (order as usize - 1_usize) < cur_knot.len() {
+        let t: Float = cur_knot[i];
+        let multiplicity: usize = cur_knot.iter().filter(|&&k| k == t).count();
+        if multiplicity < (order - 1_i32) as usize {
+            let (refined_knot, refined_cp) = knot_insert(order, &cur_knot, &cur_cp, t);
+            cur_knot = refined_knot;
+            cur_cp = refined_cp;
+        } else {
+            i += 1;
+        }
+    }
+    let n_segments: usize = (cur_cp.len() - 1_usize) / (order as usize - 1_usize);
+    let mut segments: Vec<Vec<P>> = Vec::with_capacity(n_segments);
+    for seg in 0..n_segments {
+        let start: usize = seg * (order as usize - 1_usize);
+        segments.push(cur_cp[start..start + order as usize].to_vec());
+    }
+    segments
+}
+
+/// Generate an open-uniform knot vector for `n_ctrl_pts` control points
+/// and the given `order` (degree + 1): the first and last `order` knots
+/// are clamped to 0 and 1 so the curve interpolates its first and last
+/// control points, and the interior knots are evenly spaced.
+pub fn open_uniform_knot_vector(order: i32, n_ctrl_pts: i32) -> Vec<Float> {
+    assert!(n_ctrl_pts >= order);
+    let n_interior: i32 = n_ctrl_pts - order;
+    let mut knot: Vec<Float> = Vec::with_capacity((n_ctrl_pts + order) as usize);
+    for _ in 0..order {
+        knot.push(0.0 as Float);
+    }
+    for i in 1..=n_interior {
+        knot.push(i as Float / (n_interior + 1_i32) as Float);
+    }
+    for _ in 0..order {
+        knot.push(1.0 as Float);
+    }
+    knot
+}
+
+/// Validate that `knot` is a legal, clamped knot vector for the given
+/// `order` and number of control points `np`: correct length,
+/// non-decreasing, and with the first and last `order` knots equal.
+pub fn validate_knot_vector(knot: &Vec<Float>, order: i32, np: i32) -> bool {
+    if knot.len() != (np + order) as usize {
+        return false;
+    }
+    for i in 1..knot.len() {
+        if knot[i] < knot[i - 1] {
+            return false;
+        }
+    }
+    let first: Float = knot[0];
+    if knot[..order as usize].iter().any(|&k| k != first) {
+        return false;
+    }
+    let last: Float = *knot.last().unwrap();
+    if knot[knot.len() - order as usize..].iter().any(|&k| k != last) {
+        return false;
+    }
+    true
+}
+
+/// Tessellate a NURBS patch (as described by a `ParamSet`) into a
+/// triangle mesh `Shape`.
+pub fn create_nurbs(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Shape + Send + Sync>> {
+    let nu: i32 = params.find_one_int("nu", -1);
+    let u_order: i32 = params.find_one_int("uorder", -1);
+    let u_knots: Vec<Float> = params.find_float("uknots");
+    assert_eq!(u_knots.len(), (nu + u_order) as usize);
+    let u0: Float = params.find_one_float("u0", u_knots[(u_order - 1_i32) as usize]);
+    let u1: Float = params.find_one_float("u1", u_knots[nu as usize]);
+
+    let nv: i32 = params.find_one_int("nv", -1);
+    let v_order: i32 = params.find_one_int("vorder", -1);
+    let v_knots: Vec<Float> = params.find_float("vknots");
+    assert_eq!(v_knots.len(), (nv + v_order) as usize);
+    let v0: Float = params.find_one_float("v0", v_knots[(v_order - 1_i32) as usize]);
+    let v1: Float = params.find_one_float("v1", v_knots[nv as usize]);
+
+    // get the control points, either non-rational ("P") or rational ("Pw")
+    let mut is_homogeneous: bool = false;
+    let mut p: Vec<Float> = params.find_float("P");
+    if p.is_empty() {
+        p = params.find_float("Pw");
+        assert!(
+            !p.is_empty(),
+            "Must provide control points via \"P\" or \"Pw\" to NURBS shape."
+        );
+        is_homogeneous = true;
+    }
+    let n_cps: usize = (nu * nv) as usize;
+    let mut cp: Vec<HomogeneousPoint> = Vec::with_capacity(n_cps);
+    if is_homogeneous {
+        assert_eq!(p.len() / 4, n_cps);
+        for i in 0..n_cps {
+            cp.push(HomogeneousPoint::xyzw(
+                p[4 * i],
+                p[4 * i + 1],
+                p[4 * i + 2],
+                p[4 * i + 3],
+            ));
+        }
+    } else {
+        assert_eq!(p.len() / 3, n_cps);
+        for i in 0..n_cps {
+            cp.push(HomogeneousPoint::xyzw(
+                p[3 * i],
+                p[3 * i + 1],
+                p[3 * i + 2],
+                1.0 as Float,
+            ));
+        }
+    }
+    let control_net: ControlNet<HomogeneousPoint> = ControlNet::new(nu as usize, nv as usize, cp);
+
+    // compute NURBS shape by tessellating the surface into a grid of
+    // evaluation points
+    let diceu: usize = 30_usize;
+    let dicev: usize = 30_usize;
+    let mut u_eval: Vec<Float> = Vec::with_capacity(diceu);
+    let mut v_eval: Vec<Float> = Vec::with_capacity(dicev);
+    for i in 0..diceu {
+        u_eval.push(lerp(i as Float / (diceu - 1_usize) as Float, u0, u1));
+    }
+    for i in 0..dicev {
+        v_eval.push(lerp(i as Float / (dicev - 1_usize) as Float, v0, v1));
+    }
+    let mut eval_ps: Vec<Point3f> = Vec::with_capacity(diceu * dicev);
+    let mut eval_ns: Vec<Normal3f> = Vec::with_capacity(diceu * dicev);
+    let mut uvs: Vec<Point2f> = Vec::with_capacity(diceu * dicev);
+    for v in 0..dicev {
+        for u in 0..diceu {
+            uvs.push(Point2f {
+                x: (u_eval[u] - u0) / (u1 - u0),
+                y: (v_eval[v] - v0) / (v1 - v0),
+            });
+            let mut dpdu: Vector3f = Vector3f::default();
+            let mut dpdv: Vector3f = Vector3f::default();
+            let pt: Point3f = nurbs_evaluate_surface(
+                u_order,
+                &u_knots,
+                u_eval[u],
+                v_order,
+                &v_knots,
+                v_eval[v],
+                &control_net,
+                &mut dpdu,
+                &mut dpdv,
+            );
+            eval_ps.push(pt);
+            eval_ns.push(nrm_normalize(&Normal3f::from(vec3_cross_vec3(&dpdu, &dpdv))));
+        }
+    }
+
+    // fan out the evaluation grid into a triangle mesh
+    let n_tris: usize = 2_usize * (diceu - 1_usize) * (dicev - 1_usize);
+    let mut vertex_indices: Vec<i32> = Vec::with_capacity(3_usize * n_tris);
+    for v in 0..(dicev - 1_usize) {
+        for u in 0..(diceu - 1_usize) {
+            let get_offset = |du: usize, dv: usize| -> i32 { ((v + dv) * diceu + (u + du)) as i32 };
+            vertex_indices.push(get_offset(0, 0));
+            vertex_indices.push(get_offset(1, 0));
+            vertex_indices.push(get_offset(1, 1));
+            vertex_indices.push(get_offset(0, 0));
+            vertex_indices.push(get_offset(1, 1));
+            vertex_indices.push(get_offset(0, 1));
+        }
+    }
+    let n_vertices: usize = diceu * dicev;
+    create_triangle_mesh(
+        o2w,
+        w2o,
+        reverse_orientation,
+        n_tris,
+        vertex_indices,
+        n_vertices,
+        eval_ps,
+        Vec::new(),
+        eval_ns,
+        uvs,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bezier_segments_preserve_endpoints_and_split_count() {
+        // a clamped quadratic curve with one interior knot (t = 0.5); Boehm's
+        // algorithm should raise that knot's multiplicity from 1 to
+        // `order - 1` (= 2), splitting the curve into exactly two
+        // order-many-control-point Bezier segments whose outer endpoints
+        // match the original curve's
+        let order: i32 = 3;
+        let knot: Vec<Float> = vec![0.0, 0.0, 0.0, 0.5, 1.0, 1.0, 1.0];
+        let cp: Vec<EuclideanPoint> = vec![
+            EuclideanPoint::xyz(0.0, 0.0, 0.0),
+            EuclideanPoint::xyz(1.0, 2.0, 0.0),
+            EuclideanPoint::xyz(3.0, 2.0, 0.0),
+            EuclideanPoint::xyz(4.0, 0.0, 0.0),
+        ];
+        let segments: Vec<Vec<EuclideanPoint>> = nurbs_to_bezier_segments(order, &knot, &cp);
+        assert_eq!(segments.len(), 2);
+        for segment in &segments {
+            assert_eq!(segment.len(), order as usize);
+        }
+        assert_eq!(segments[0][0].c, cp[0].c);
+        assert_eq!(segments[1].last().unwrap().c, cp[3].c);
+    }
 }